@@ -0,0 +1,354 @@
+use redox::Box;
+use redox::fs::file::File;
+use redox::string::*;
+use redox::vec::Vec;
+use redox::io::{Read, Write, Seek, SeekFrom};
+
+const WINDOW: usize = 8192;
+const MIN_MATCH: usize = 4;
+const MAX_MATCH: usize = 131;
+const MAX_LITERAL_RUN: usize = 128;
+const HASH_SIZE: usize = 4096;
+
+fn hash(buf: &[u8], i: usize) -> usize {
+    let word = (buf[i] as usize) | (buf[i + 1] as usize) << 8 | (buf[i + 2] as usize) << 16 | (buf[i + 3] as usize) << 24;
+    (word.wrapping_mul(2654435761)) >> (32 - 12) & (HASH_SIZE - 1)
+}
+
+fn match_length(buf: &[u8], a: usize, b: usize, limit: usize) -> usize {
+    let mut len = 0;
+    while len < limit && a + len < buf.len() && buf[a + len] == buf[b + len] {
+        len += 1;
+    }
+    len
+}
+
+fn write_varint(out: &mut Vec<u8>, value: usize) {
+    let mut v = value;
+    loop {
+        let mut byte = (v & 0x7F) as u8;
+        v >>= 7;
+        if v != 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if v == 0 {
+            break;
+        }
+    }
+}
+
+fn read_varint<F: FnMut() -> Option<u8>>(mut next: F) -> Option<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = match next() {
+            Option::Some(byte) => byte,
+            Option::None => return Option::None
+        };
+
+        value |= ((byte & 0x7F) as usize) << shift;
+
+        if byte & 0x80 == 0 {
+            return Option::Some(value);
+        }
+
+        shift += 7;
+    }
+}
+
+fn emit_literals(out: &mut Vec<u8>, literals: &[u8]) {
+    let mut i = 0;
+    while i < literals.len() {
+        let mut run = literals.len() - i;
+        if run > MAX_LITERAL_RUN {
+            run = MAX_LITERAL_RUN;
+        }
+
+        out.push((run - 1) as u8);
+
+        let mut j = 0;
+        while j < run {
+            out.push(literals[i + j]);
+            j += 1;
+        }
+
+        i += run;
+    }
+}
+
+// LZ77 over an 8 KiB window: hash the next 4 bytes, walk the hash chain for
+// the longest prior match, and emit either a copy or a literal run.
+pub fn compress_block(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_varint(&mut out, input.len());
+
+    let mut head: Vec<isize> = Vec::new();
+    let mut i = 0;
+    while i < HASH_SIZE {
+        head.push(-1);
+        i += 1;
+    }
+
+    let mut prev: Vec<isize> = Vec::new();
+
+    let mut literal_start = 0;
+    let mut i = 0;
+
+    while i < input.len() {
+        let mut best_len = 0;
+        let mut best_pos = 0;
+
+        if i + MIN_MATCH <= input.len() {
+            let key = hash(input, i);
+            let mut candidate = head[key];
+            let mut tries = 0;
+
+            while candidate >= 0 && (i - candidate as usize) <= WINDOW && tries < 32 {
+                let len = match_length(input, i, candidate as usize, MAX_MATCH);
+                if len > best_len {
+                    best_len = len;
+                    best_pos = candidate as usize;
+                }
+                candidate = prev[candidate as usize];
+                tries += 1;
+            }
+
+            prev.push(head[key]);
+            head[key] = i as isize;
+        } else {
+            prev.push(-1);
+        }
+
+        if best_len >= MIN_MATCH {
+            if literal_start < i {
+                emit_literals(&mut out, &input[literal_start..i]);
+            }
+
+            let distance = i - best_pos;
+            out.push(0x80 | (best_len - MIN_MATCH) as u8);
+            out.push(distance as u8);
+            out.push((distance >> 8) as u8);
+
+            let end = i + best_len;
+            i += 1;
+            while i < end && i + MIN_MATCH <= input.len() {
+                let key = hash(input, i);
+                prev.push(head[key]);
+                head[key] = i as isize;
+                i += 1;
+            }
+            i = end;
+            literal_start = i;
+        } else {
+            i += 1;
+        }
+    }
+
+    if literal_start < input.len() {
+        emit_literals(&mut out, &input[literal_start..]);
+    }
+
+    out
+}
+
+pub fn decompress_block<F: FnMut() -> Option<u8>>(mut next: F) -> Option<Vec<u8>> {
+    let uncompressed_len = match read_varint(&mut next) {
+        Option::Some(len) => len,
+        Option::None => return Option::None
+    };
+
+    let mut out = Vec::new();
+
+    while out.len() < uncompressed_len {
+        let tag = match next() {
+            Option::Some(tag) => tag,
+            Option::None => return Option::None
+        };
+
+        if tag & 0x80 == 0 {
+            let run = (tag as usize) + 1;
+            let mut i = 0;
+            while i < run {
+                match next() {
+                    Option::Some(byte) => out.push(byte),
+                    Option::None => return Option::None
+                }
+                i += 1;
+            }
+        } else {
+            let len = ((tag & 0x7F) as usize) + MIN_MATCH;
+
+            let lo = match next() {
+                Option::Some(byte) => byte,
+                Option::None => return Option::None
+            };
+            let hi = match next() {
+                Option::Some(byte) => byte,
+                Option::None => return Option::None
+            };
+            let distance = (lo as usize) | (hi as usize) << 8;
+
+            if distance == 0 || distance > out.len() {
+                return Option::None;
+            }
+
+            let start = out.len() - distance;
+            let mut i = 0;
+            while i < len {
+                let byte = out[start + i];
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    Option::Some(out)
+}
+
+// Writes the entire buffer to `inner`, looping over short writes instead of
+// assuming a single call drains it. A Some(0) is treated as a stalled
+// resource rather than success, so a compressed block can never be
+// partially flushed without this returning an error.
+fn write_all(inner: &mut File, buf: &[u8]) -> bool {
+    let mut written = 0;
+    while written < buf.len() {
+        match inner.write(&buf[written..]) {
+            Some(0) => return false,
+            Some(n) => written += n,
+            None => return false
+        }
+    }
+
+    true
+}
+
+pub struct Resource {
+    inner: File,
+    read_buf: Vec<u8>,
+    read_pos: usize,
+    write_buf: Vec<u8>
+}
+
+impl Resource {
+    pub fn new(inner: File) -> Resource {
+        Resource {
+            inner: inner,
+            read_buf: Vec::new(),
+            read_pos: 0,
+            write_buf: Vec::new()
+        }
+    }
+
+    pub fn dup(&self) -> Option<Box<Self>> {
+        match self.inner.dup() {
+            Some(inner) => Some(box Resource::new(inner)),
+            None => None
+        }
+    }
+
+    pub fn path(&self, buf: &mut [u8]) -> Option<usize> {
+        self.inner.path(buf)
+    }
+
+    pub fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
+        if self.read_pos >= self.read_buf.len() {
+            let mut first = [0; 1];
+            match self.inner.read(&mut first) {
+                Some(0) => return Some(0),
+                Some(1) => (),
+                _ => return None
+            }
+
+            let mut first_byte = Some(first[0]);
+            let inner = &mut self.inner;
+            let block = decompress_block(|| {
+                if let Some(byte) = first_byte.take() {
+                    return Some(byte);
+                }
+
+                let mut byte = [0; 1];
+                match inner.read(&mut byte) {
+                    Some(1) => Some(byte[0]),
+                    _ => None
+                }
+            });
+
+            match block {
+                Some(block) => {
+                    self.read_buf = block;
+                    self.read_pos = 0;
+                },
+                None => return None
+            }
+        }
+
+        let mut i = 0;
+        while i < buf.len() && self.read_pos < self.read_buf.len() {
+            buf[i] = self.read_buf[self.read_pos];
+            self.read_pos += 1;
+            i += 1;
+        }
+
+        Some(i)
+    }
+
+    pub fn write(&mut self, buf: &[u8]) -> Option<usize> {
+        self.write_buf.push_all(buf);
+
+        while self.write_buf.len() >= WINDOW {
+            let mut block = Vec::new();
+            let mut i = 0;
+            while i < WINDOW {
+                block.push(self.write_buf[i]);
+                i += 1;
+            }
+
+            let compressed = compress_block(&block);
+            if !write_all(&mut self.inner, &compressed) {
+                return None;
+            }
+
+            let mut rest = Vec::new();
+            let mut i = WINDOW;
+            while i < self.write_buf.len() {
+                rest.push(self.write_buf[i]);
+                i += 1;
+            }
+            self.write_buf = rest;
+        }
+
+        Some(buf.len())
+    }
+
+    pub fn seek(&mut self, seek: SeekFrom) -> Option<usize> {
+        None
+    }
+
+    pub fn sync(&mut self) -> bool {
+        if !self.write_buf.is_empty() {
+            let compressed = compress_block(&self.write_buf);
+            if !write_all(&mut self.inner, &compressed) {
+                return false;
+            }
+            self.write_buf = Vec::new();
+        }
+
+        self.inner.sync()
+    }
+}
+
+pub struct Scheme;
+
+impl Scheme {
+    pub fn new() -> Box<Self> {
+        box Scheme
+    }
+
+    pub fn open(&mut self, path: &str) -> Option<Box<Resource>> {
+        match File::open(path) {
+            Some(inner) => Some(box Resource::new(inner)),
+            None => None
+        }
+    }
+}
@@ -3,16 +3,75 @@ use redox::fs::file::File;
 use redox::string::*;
 use redox::io::{Read, Write, Seek, SeekFrom};
 
+use common::scheduler::*;
+
+struct CursorLock {
+    locked: bool,
+    refs: usize
+}
+
 pub struct Resource {
-    file: File
+    file: File,
+    // Shared by every handle `dup`'d from the same open: the underlying
+    // file description (and its seek position) is shared too, so
+    // read_at/write_at must serialize their seek/io/restore dance across
+    // all of them, not just guard each handle against itself. The check
+    // and the acquire happen inside one start_no_ints/end_no_ints section
+    // so two handles can't both observe `locked == false` and both
+    // proceed; waiters park on the lock's address instead of spinning.
+    cursor_lock: *mut CursorLock
 }
 
 impl Resource {
+    fn lock_cursor(&self) {
+        loop {
+            let acquired;
+            unsafe {
+                let reenable = start_no_ints();
+
+                if (*self.cursor_lock).locked {
+                    acquired = false;
+                    register_blocked(self.cursor_lock as usize);
+                } else {
+                    (*self.cursor_lock).locked = true;
+                    acquired = true;
+                }
+
+                end_no_ints(reenable);
+            }
+
+            if acquired {
+                return;
+            }
+
+            unsafe { park(self.cursor_lock as usize); }
+        }
+    }
+
+    fn unlock_cursor(&self) {
+        unsafe {
+            let reenable = start_no_ints();
+            (*self.cursor_lock).locked = false;
+            end_no_ints(reenable);
+
+            wake(self.cursor_lock as usize);
+        }
+    }
+
     pub fn dup(&self) -> Option<Box<Self>> {
         match self.file.dup() {
-            Some(file) => Some(box Resource {
-                file: file
-            }),
+            Some(file) => {
+                unsafe {
+                    let reenable = start_no_ints();
+                    (*self.cursor_lock).refs += 1;
+                    end_no_ints(reenable);
+                }
+
+                Some(box Resource {
+                    file: file,
+                    cursor_lock: self.cursor_lock
+                })
+            },
             None => None
         }
     }
@@ -33,11 +92,78 @@ impl Resource {
         self.file.seek(seek)
     }
 
+    pub fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Option<usize> {
+        self.lock_cursor();
+
+        let original = match self.file.seek(SeekFrom::Current(0)) {
+            Some(pos) => pos,
+            None => {
+                self.unlock_cursor();
+                return None;
+            }
+        };
+
+        if self.file.seek(SeekFrom::Start(offset)).is_none() {
+            self.unlock_cursor();
+            return None;
+        }
+
+        let result = self.file.read(buf);
+
+        self.file.seek(SeekFrom::Start(original));
+
+        self.unlock_cursor();
+
+        result
+    }
+
+    pub fn write_at(&mut self, offset: usize, buf: &[u8]) -> Option<usize> {
+        self.lock_cursor();
+
+        let original = match self.file.seek(SeekFrom::Current(0)) {
+            Some(pos) => pos,
+            None => {
+                self.unlock_cursor();
+                return None;
+            }
+        };
+
+        if self.file.seek(SeekFrom::Start(offset)).is_none() {
+            self.unlock_cursor();
+            return None;
+        }
+
+        let result = self.file.write(buf);
+
+        self.file.seek(SeekFrom::Start(original));
+
+        self.unlock_cursor();
+
+        result
+    }
+
     pub fn sync(&mut self) -> bool {
         self.file.sync()
     }
 }
 
+impl Drop for Resource {
+    fn drop(&mut self) {
+        unsafe {
+            let reenable = start_no_ints();
+
+            (*self.cursor_lock).refs -= 1;
+            let last_ref = (*self.cursor_lock).refs == 0;
+
+            end_no_ints(reenable);
+
+            if last_ref {
+                drop(Box::from_raw(self.cursor_lock));
+            }
+        }
+    }
+}
+
 pub struct Scheme;
 
 impl Scheme {
@@ -48,9 +174,10 @@ impl Scheme {
     pub fn open(&mut self, path: &str) -> Option<Box<Resource>> {
         match File::open(&("example:".to_string() + path)) {
             Some(file) => Some(box Resource {
-                file: file
+                file: file,
+                cursor_lock: Box::into_raw(box CursorLock { locked: false, refs: 1 })
             }),
             None => None
         }
     }
-}
\ No newline at end of file
+}
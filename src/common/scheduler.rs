@@ -0,0 +1,80 @@
+use alloc::boxed::Box;
+use collections::vec::Vec;
+
+static mut blocked: *mut Vec<usize> = 0 as *mut Vec<usize>;
+
+unsafe fn blocked_mut() -> &'static mut Vec<usize> {
+    if blocked as usize == 0 {
+        blocked = Box::into_raw(box Vec::new());
+    }
+
+    &mut *blocked
+}
+
+unsafe fn is_blocked(token: usize) -> bool {
+    let mut i = 0;
+    while i < blocked_mut().len() {
+        match blocked_mut().get(i) {
+            Option::Some(waiting) => if *waiting == token {
+                return true;
+            },
+            Option::None => break
+        }
+        i += 1;
+    }
+
+    false
+}
+
+// Registers `token` as blocked. Must be called from inside the same
+// start_no_ints/end_no_ints section that just observed the wait condition
+// is not yet satisfied, so the check and the registration are one atomic
+// step from an interrupt handler's point of view - otherwise a wake() that
+// lands between the check and the registration is missed forever. A waiter
+// already registered (e.g. woken spuriously and re-checked) is not
+// re-pushed, so `blocked` stays bounded to one entry per outstanding wait.
+pub unsafe fn register_blocked(token: usize) {
+    if !is_blocked(token) {
+        blocked_mut().push(token);
+    }
+}
+
+// Parks the caller until `wake(token)` removes the registration made by
+// register_blocked. Halts the CPU between checks instead of busy-spinning a
+// yield: with interrupts re-enabled, hlt does nothing until the next
+// interrupt (the NIC IRQ that calls wake(), or any other), so an idle wait
+// burns no cycles instead of re-entering the scheduler every tick.
+pub unsafe fn park(token: usize) {
+    loop {
+        let reenable = start_no_ints();
+        let still_blocked = is_blocked(token);
+        end_no_ints(reenable);
+
+        if !still_blocked {
+            break;
+        }
+
+        asm!("sti
+              hlt" : : : : "volatile");
+    }
+}
+
+pub unsafe fn wake(token: usize) {
+    let reenable = start_no_ints();
+
+    let mut i = 0;
+    while i < blocked_mut().len() {
+        let matches = match blocked_mut().get(i) {
+            Option::Some(waiting) => *waiting == token,
+            Option::None => false
+        };
+
+        if matches {
+            blocked_mut().remove(i);
+        } else {
+            i += 1;
+        }
+    }
+
+    end_no_ints(reenable);
+}
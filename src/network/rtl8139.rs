@@ -10,18 +10,89 @@ use network::ethernet::*;
 
 use programs::common::*;
 
+pub enum Filter {
+    Promiscuous,
+    EtherType(u16),
+    Destination(MACAddr)
+}
+
+impl Filter {
+    pub fn from_url(url: &URL) -> Filter {
+        let url_string = url.to_string();
+
+        let path = match url_string.find("://") {
+            Option::Some(i) => &url_string[i + 3..],
+            Option::None => ""
+        };
+
+        let mut parts = path.splitn(2, '/');
+        let kind = parts.next().unwrap_or("");
+        let value = parts.next().unwrap_or("");
+
+        match kind {
+            "ethertype" if value.starts_with("0x") => {
+                match u16::from_str_radix(&value[2..], 16) {
+                    Result::Ok(ethertype) => Filter::EtherType(ethertype),
+                    Result::Err(_) => Filter::Promiscuous
+                }
+            },
+            "mac" => {
+                match Filter::parse_mac(value) {
+                    Option::Some(mac) => Filter::Destination(mac),
+                    Option::None => Filter::Promiscuous
+                }
+            },
+            _ => Filter::Promiscuous
+        }
+    }
+
+    fn parse_mac(value: &str) -> Option<MACAddr> {
+        let mut bytes = [0u8; 6];
+        let mut i = 0;
+
+        for part in value.split(':') {
+            if i >= 6 {
+                return Option::None;
+            }
+
+            match u8::from_str_radix(part, 16) {
+                Result::Ok(byte) => bytes[i] = byte,
+                Result::Err(_) => return Option::None
+            }
+
+            i += 1;
+        }
+
+        if i == 6 {
+            Option::Some(MACAddr { bytes: bytes })
+        } else {
+            Option::None
+        }
+    }
+
+    pub fn matches(&self, dst: &MACAddr, ethertype: u16) -> bool {
+        match *self {
+            Filter::Promiscuous => true,
+            Filter::EtherType(filter_ethertype) => ethertype == filter_ethertype,
+            Filter::Destination(ref filter_mac) => dst.bytes == filter_mac.bytes
+        }
+    }
+}
+
 pub struct RTL8139Resource {
     pub nic: *mut RTL8139,
     pub ptr: *mut RTL8139Resource,
+    pub filter: Filter,
     pub inbound: Queue<Vec<u8>>,
     pub outbound: Queue<Vec<u8>>
 }
 
 impl RTL8139Resource {
-    pub fn new(nic: &mut RTL8139) -> Box<RTL8139Resource> {
+    pub fn new(nic: &mut RTL8139, filter: Filter) -> Box<RTL8139Resource> {
         let mut ret = box RTL8139Resource {
             nic: nic,
             ptr: 0 as *mut RTL8139Resource,
+            filter: filter,
             inbound: Queue::new(),
             outbound: Queue::new()
         };
@@ -57,6 +128,9 @@ impl Resource for RTL8139Resource {
             unsafe{
                 let reenable = start_no_ints();
                 option = self.inbound.pop();
+                if option.is_none() {
+                    register_blocked(self.ptr as usize);
+                }
                 end_no_ints(reenable);
             }
 
@@ -72,7 +146,7 @@ impl Resource for RTL8139Resource {
                 return Option::Some(i);
             }
 
-            sys_yield();
+            unsafe { park(self.ptr as usize); }
         }
     }
 
@@ -85,6 +159,9 @@ impl Resource for RTL8139Resource {
             unsafe{
                 let reenable = start_no_ints();
                 option = self.inbound.pop();
+                if option.is_none() {
+                    register_blocked(self.ptr as usize);
+                }
                 end_no_ints(reenable);
             }
 
@@ -93,7 +170,7 @@ impl Resource for RTL8139Resource {
                 return Option::Some(bytes.len());
             }
 
-            sys_yield();
+            unsafe { park(self.ptr as usize); }
         }
     }
 
@@ -115,12 +192,23 @@ impl Resource for RTL8139Resource {
         return Option::None;
     }
 
+    fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Option<usize> {
+        return Option::None;
+    }
+
+    fn write_at(&mut self, offset: usize, buf: &[u8]) -> Option<usize> {
+        return Option::None;
+    }
+
     fn flush(&mut self) -> bool {
         loop {
             let len;
             unsafe{
                 let reenable = start_no_ints();
                 len = self.outbound.len();
+                if len != 0 {
+                    register_blocked(self.ptr as usize);
+                }
                 end_no_ints(reenable);
             }
 
@@ -128,7 +216,7 @@ impl Resource for RTL8139Resource {
                 return true;
             }
 
-            sys_yield();
+            unsafe { park(self.ptr as usize); }
         }
     }
 }
@@ -180,7 +268,7 @@ impl SessionItem for RTL8139 {
     }
 
     fn open(&mut self, url: &URL) -> Box<Resource> {
-        return RTL8139Resource::new(self);
+        return RTL8139Resource::new(self, Filter::from_url(url));
     }
 
     fn on_irq(&mut self, irq: u8){
@@ -221,8 +309,50 @@ impl RTL8139 {
             let frame_addr = receive_buffer + capr + 4;
             let frame_len = *((receive_buffer + capr + 2) as *const u16) as usize;
 
-            for resource in self.resources.iter() {
-                (**resource).inbound.push(Vec::from_raw_buf(frame_addr as *const u8, frame_len - 4));
+            // frame_len includes the 4-byte CRC, so frame_len - 4 is the
+            // payload length handed to from_raw_buf below; skip delivery
+            // entirely rather than underflow that subtraction on a runt
+            // frame. A full Ethernet II header (6 + 6 + 2 bytes) needs at
+            // least 18 bytes of frame to be safe to dereference; below that,
+            // frames still reach promiscuous listeners, just unfiltered.
+            let has_header = frame_len >= 18;
+
+            let dst = if has_header {
+                MACAddr {
+                    bytes: [
+                        *((frame_addr) as *const u8),
+                        *((frame_addr + 1) as *const u8),
+                        *((frame_addr + 2) as *const u8),
+                        *((frame_addr + 3) as *const u8),
+                        *((frame_addr + 4) as *const u8),
+                        *((frame_addr + 5) as *const u8)
+                    ]
+                }
+            } else {
+                MACAddr { bytes: [0; 6] }
+            };
+            let ethertype = if has_header {
+                (*((frame_addr + 12) as *const u8) as u16) << 8 | *((frame_addr + 13) as *const u8) as u16
+            } else {
+                0
+            };
+
+            if frame_len >= 4 {
+                for resource in self.resources.iter() {
+                    let deliver = if has_header {
+                        (**resource).filter.matches(&dst, ethertype)
+                    } else {
+                        match (**resource).filter {
+                            Filter::Promiscuous => true,
+                            _ => false
+                        }
+                    };
+
+                    if deliver {
+                        (**resource).inbound.push(Vec::from_raw_buf(frame_addr as *const u8, frame_len - 4));
+                        wake(*resource as usize);
+                    }
+                }
             }
 
             capr = capr + frame_len + 4;
@@ -272,6 +402,8 @@ impl RTL8139 {
 
                                         outd(base + 0x20 + (self.tx_i as u16) * 4, tx_buffer);
                                         outd(base + 0x10 + (self.tx_i as u16) * 4, bytes.len() as u32 & 0x1FFF);
+
+                                        wake(*resource as usize);
                                     }else{
                                         dl();
                                         d("RTL8139: Frame too long for transmit: ");